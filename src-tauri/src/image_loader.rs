@@ -14,6 +14,52 @@ use serde_json::{Value, from_value};
 //use tauri::path;
 //use std::fs;
 
+/// Compositing mode for an AI patch. `Normal` is plain source-over (the
+/// historical behavior); the others blend the patch against the base pixel
+/// before the mask-driven alpha mix, letting a patch darken or lighten a region
+/// instead of fully replacing it.
+#[derive(Deserialize, Default, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+enum BlendMode {
+    #[default]
+    Normal,
+    Multiply,
+    Screen,
+    Overlay,
+    #[serde(rename = "soft-light", alias = "softlight")]
+    SoftLight,
+}
+
+impl BlendMode {
+    /// Blends a single base/patch channel pair, both in `[0, 1]`.
+    fn blend(self, base: f32, patch: f32) -> f32 {
+        match self {
+            BlendMode::Normal => patch,
+            BlendMode::Multiply => base * patch,
+            BlendMode::Screen => 1.0 - (1.0 - base) * (1.0 - patch),
+            BlendMode::Overlay => {
+                if base < 0.5 {
+                    2.0 * base * patch
+                } else {
+                    1.0 - 2.0 * (1.0 - base) * (1.0 - patch)
+                }
+            }
+            BlendMode::SoftLight => {
+                if patch <= 0.5 {
+                    base - (1.0 - 2.0 * patch) * base * (1.0 - base)
+                } else {
+                    let d = if base <= 0.25 {
+                        ((16.0 * base - 12.0) * base + 4.0) * base
+                    } else {
+                        base.sqrt()
+                    };
+                    base + (2.0 * patch - 1.0) * (d - base)
+                }
+            }
+        }
+    }
+}
+
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
 struct PatchMaskInfo {
@@ -72,6 +118,34 @@ pub fn load_image_with_orientation(bytes: &[u8]) -> Result<DynamicImage> {
     Ok(image)
 }
 
+/// Losslessly re-optimizes an already-encoded PNG, returning the smaller of the
+/// input and the optimized result.
+///
+/// `level` (clamped to 0..=6) selects the effort preset: oxipng tries the
+/// configured zlib filter strategies per scanline — None, Sub, Up, Average,
+/// Paeth and the adaptive minimum-sum-of-absolute-differences heuristic — and
+/// keeps the smallest encoding, raising the number of strategies tried at higher
+/// levels. Above level 0 it also strips non-essential ancillary chunks while
+/// preserving the ICC profile (orientation is already baked into the pixels by
+/// `load_image_with_orientation`, so the eXIf tag is redundant on export).
+pub fn optimize_png(bytes: &[u8], level: u8) -> Result<Vec<u8>> {
+    let mut options = oxipng::Options::from_preset(level.min(6));
+    options.strip = if level == 0 {
+        oxipng::StripChunks::None
+    } else {
+        oxipng::StripChunks::Safe
+    };
+
+    let optimized = oxipng::optimize_from_memory(bytes, &options)
+        .context("Failed to optimize PNG")?;
+
+    if optimized.len() < bytes.len() {
+        Ok(optimized)
+    } else {
+        Ok(bytes.to_vec())
+    }
+}
+
 pub fn composite_patches_on_image(
     base_image: &DynamicImage,
     current_adjustments: &Value,
@@ -133,6 +207,11 @@ pub fn composite_patches_on_image(
             .get("color")
             .and_then(|v| v.as_str())
             .context("Missing color data")?;
+        let blend_mode: BlendMode = patch_data
+            .get("blendMode")
+            .and_then(|v| from_value(v.clone()).ok())
+            .unwrap_or_default();
+
         let color_bytes = general_purpose::STANDARD.decode(color_b64)?;
         let mut color_image = image::load_from_memory(&color_bytes)?.to_rgb8();
 
@@ -159,15 +238,16 @@ pub fn composite_patches_on_image(
                         let base_g = row[x * 4 + 1];
                         let base_b = row[x * 4 + 2];
 
-                        row[x * 4 + 0] = (patch_pixel[0] as f32 * alpha
-                            + base_r as f32 * one_minus_alpha)
-                            .round() as u8;
-                        row[x * 4 + 1] = (patch_pixel[1] as f32 * alpha
-                            + base_g as f32 * one_minus_alpha)
-                            .round() as u8;
-                        row[x * 4 + 2] = (patch_pixel[2] as f32 * alpha
-                            + base_b as f32 * one_minus_alpha)
-                            .round() as u8;
+                        // Blend the patch against the base first (in [0, 1]),
+                        // then mix by the mask-driven alpha exactly as the
+                        // normal source-over path does.
+                        let blended_r = blend_mode.blend(base_r as f32 / 255.0, patch_pixel[0] as f32 / 255.0) * 255.0;
+                        let blended_g = blend_mode.blend(base_g as f32 / 255.0, patch_pixel[1] as f32 / 255.0) * 255.0;
+                        let blended_b = blend_mode.blend(base_b as f32 / 255.0, patch_pixel[2] as f32 / 255.0) * 255.0;
+
+                        row[x * 4 + 0] = (blended_r * alpha + base_r as f32 * one_minus_alpha).round() as u8;
+                        row[x * 4 + 1] = (blended_g * alpha + base_g as f32 * one_minus_alpha).round() as u8;
+                        row[x * 4 + 2] = (blended_b * alpha + base_b as f32 * one_minus_alpha).round() as u8;
                     }
                 }
             });
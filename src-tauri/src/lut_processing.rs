@@ -1,13 +1,39 @@
 use anyhow::{anyhow, Result};
-use image::{DynamicImage, GenericImageView};
+use image::{ColorType, DynamicImage, GenericImageView};
+use rayon::prelude::*;
 use std::fs::File;
 use std::io::{BufRead, BufReader};
 use std::path::Path;
 
+/// Dimensionality of a parsed LUT. 1D LUTs are applied as an independent
+/// per-channel curve; 3D LUTs are interpolated tetrahedrally.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LutKind {
+    OneDimensional,
+    ThreeDimensional,
+}
+
 #[derive(Debug)]
 pub struct Lut {
+    pub kind: LutKind,
     pub size: u32,
     pub data: Vec<f32>,
+    /// Lower bound of the input domain per channel (defaults to [0, 0, 0]).
+    pub domain_min: [f32; 3],
+    /// Upper bound of the input domain per channel (defaults to [1, 1, 1]).
+    pub domain_max: [f32; 3],
+}
+
+impl Lut {
+    fn new_3d(size: u32, data: Vec<f32>) -> Self {
+        Lut {
+            kind: LutKind::ThreeDimensional,
+            size,
+            data,
+            domain_min: [0.0; 3],
+            domain_max: [1.0; 3],
+        }
+    }
 }
 
 fn parse_cube(path: &Path) -> Result<Lut> {
@@ -15,7 +41,10 @@ fn parse_cube(path: &Path) -> Result<Lut> {
     let reader = BufReader::new(file);
 
     let mut size: Option<u32> = None;
+    let mut kind: Option<LutKind> = None;
     let mut data: Vec<f32> = Vec::new();
+    let mut domain_min = [0.0_f32; 3];
+    let mut domain_max = [1.0_f32; 3];
 
     for line in reader.lines() {
         let line = line?;
@@ -34,8 +63,17 @@ fn parse_cube(path: &Path) -> Result<Lut> {
             "LUT_3D_SIZE" => {
                 if parts.len() > 1 {
                     size = Some(parts[1].parse()?);
+                    kind = Some(LutKind::ThreeDimensional);
                 }
             }
+            "LUT_1D_SIZE" => {
+                if parts.len() > 1 {
+                    size = Some(parts[1].parse()?);
+                    kind = Some(LutKind::OneDimensional);
+                }
+            }
+            "DOMAIN_MIN" => domain_min = parse_domain(&parts)?,
+            "DOMAIN_MAX" => domain_max = parse_domain(&parts)?,
             _ => {
                 if size.is_some() {
                     let r: f32 = parts.get(0).ok_or(anyhow!("Missing R value"))?.parse()?;
@@ -49,16 +87,36 @@ fn parse_cube(path: &Path) -> Result<Lut> {
         }
     }
 
-    let lut_size = size.ok_or(anyhow!("LUT_3D_SIZE not found in .cube file"))?;
-    if data.len() != (lut_size * lut_size * lut_size * 3) as usize {
+    let lut_size = size.ok_or(anyhow!("LUT_3D_SIZE or LUT_1D_SIZE not found in .cube file"))?;
+    let kind = kind.unwrap_or(LutKind::ThreeDimensional);
+
+    let expected = match kind {
+        LutKind::OneDimensional => (lut_size * 3) as usize,
+        LutKind::ThreeDimensional => (lut_size * lut_size * lut_size * 3) as usize,
+    };
+    if data.len() != expected {
         return Err(anyhow!(
             "LUT data size mismatch. Expected {}, found {}",
-            lut_size * lut_size * lut_size * 3,
+            expected,
             data.len()
         ));
     }
 
-    Ok(Lut { size: lut_size, data })
+    Ok(Lut {
+        kind,
+        size: lut_size,
+        data,
+        domain_min,
+        domain_max,
+    })
+}
+
+/// Parses the three floats following a `DOMAIN_MIN`/`DOMAIN_MAX` keyword.
+fn parse_domain(parts: &[&str]) -> Result<[f32; 3]> {
+    let r: f32 = parts.get(1).ok_or(anyhow!("Missing domain R value"))?.parse()?;
+    let g: f32 = parts.get(2).ok_or(anyhow!("Missing domain G value"))?.parse()?;
+    let b: f32 = parts.get(3).ok_or(anyhow!("Missing domain B value"))?.parse()?;
+    Ok([r, g, b])
 }
 
 fn parse_3dl(path: &Path) -> Result<Lut> {
@@ -94,7 +152,7 @@ fn parse_3dl(path: &Path) -> Result<Lut> {
         return Err(anyhow!("Invalid 3DL LUT data size"));
     }
 
-    Ok(Lut { size, data })
+    Ok(Lut::new_3d(size, data))
 }
 
 fn parse_hald(image: DynamicImage) -> Result<Lut> {
@@ -114,15 +172,174 @@ fn parse_hald(image: DynamicImage) -> Result<Lut> {
     }
 
     let mut data = Vec::with_capacity((total_pixels * 3) as usize);
-    let rgb_image = image.to_rgb8();
 
-    for pixel in rgb_image.pixels() {
-        data.push(pixel[0] as f32 / 255.0);
-        data.push(pixel[1] as f32 / 255.0);
-        data.push(pixel[2] as f32 / 255.0);
+    // Decode at the source bit depth so 16-bit HALD exports (common from
+    // grading tools as 16-bit PNG/TIFF) keep their precision instead of being
+    // crushed to 8-bit, which bands smooth gradients. `Lut.data` is f32, so no
+    // precision is lost downstream either way.
+    match image.color() {
+        ColorType::Rgb16 | ColorType::Rgba16 | ColorType::L16 | ColorType::La16 => {
+            let rgb_image = image.to_rgb16();
+            for pixel in rgb_image.pixels() {
+                data.push(pixel[0] as f32 / 65535.0);
+                data.push(pixel[1] as f32 / 65535.0);
+                data.push(pixel[2] as f32 / 65535.0);
+            }
+        }
+        _ => {
+            let rgb_image = image.to_rgb8();
+            for pixel in rgb_image.pixels() {
+                data.push(pixel[0] as f32 / 255.0);
+                data.push(pixel[1] as f32 / 255.0);
+                data.push(pixel[2] as f32 / 255.0);
+            }
+        }
     }
 
-    Ok(Lut { size, data })
+    Ok(Lut::new_3d(size, data))
+}
+
+/// Applies a `Lut` (1D or 3D) to an image.
+///
+/// 3D LUTs are interpolated tetrahedrally; 1D LUTs are applied as an
+/// independent per-channel curve. Inputs are remapped from the LUT's stored
+/// domain into lattice coordinates before lookup, so log-to-Rec709 LUTs that
+/// ship with a non-unit `DOMAIN_MIN`/`DOMAIN_MAX` resolve correctly. The result
+/// is blended with the original pixel by `strength` (0.0 = original, 1.0 = full
+/// LUT), mirroring how `composite_patches_on_image` mixes by a per-pixel alpha.
+pub fn apply_lut(image: &DynamicImage, lut: &Lut, strength: f32) -> DynamicImage {
+    apply_lut_chain(image, None, lut, strength)
+}
+
+/// Applies an optional 1D `shaper` LUT in front of `lut`, then blends by
+/// `strength`. The shaper reshapes the input (e.g. a log curve) before the 3D
+/// lookup; passing `None` applies `lut` on its own.
+pub fn apply_lut_chain(
+    image: &DynamicImage,
+    shaper: Option<&Lut>,
+    lut: &Lut,
+    strength: f32,
+) -> DynamicImage {
+    let (width, _height) = image.dimensions();
+    let mut rgba = image.to_rgba8();
+
+    rgba.par_chunks_mut(width as usize * 4).for_each(|row| {
+        for x in 0..width as usize {
+            let base_r = row[x * 4 + 0] as f32 / 255.0;
+            let base_g = row[x * 4 + 1] as f32 / 255.0;
+            let base_b = row[x * 4 + 2] as f32 / 255.0;
+
+            let (sr, sg, sb) = match shaper {
+                Some(s) => sample_lut(s, base_r, base_g, base_b),
+                None => (base_r, base_g, base_b),
+            };
+            let (lr, lg, lb) = sample_lut(lut, sr, sg, sb);
+
+            row[x * 4 + 0] = ((base_r + (lr - base_r) * strength) * 255.0).round().clamp(0.0, 255.0) as u8;
+            row[x * 4 + 1] = ((base_g + (lg - base_g) * strength) * 255.0).round().clamp(0.0, 255.0) as u8;
+            row[x * 4 + 2] = ((base_b + (lb - base_b) * strength) * 255.0).round().clamp(0.0, 255.0) as u8;
+        }
+    });
+
+    DynamicImage::ImageRgba8(rgba)
+}
+
+/// Dispatches a single RGB lookup to the tetrahedral (3D) or per-channel (1D)
+/// sampler, remapping each channel from the LUT's domain into [0, 1] first.
+fn sample_lut(lut: &Lut, r: f32, g: f32, b: f32) -> (f32, f32, f32) {
+    let remap = |v: f32, c: usize| {
+        let span = lut.domain_max[c] - lut.domain_min[c];
+        if span.abs() < f32::EPSILON {
+            0.0
+        } else {
+            ((v - lut.domain_min[c]) / span).clamp(0.0, 1.0)
+        }
+    };
+    let nr = remap(r, 0);
+    let ng = remap(g, 1);
+    let nb = remap(b, 2);
+
+    let size = lut.size as usize;
+    match lut.kind {
+        LutKind::OneDimensional => sample_1d(lut, nr, ng, nb, size),
+        LutKind::ThreeDimensional => sample_tetrahedral(lut, nr, ng, nb, size, (size - 1) as f32),
+    }
+}
+
+/// Looks up each channel independently in a 1D LUT, interpolating linearly
+/// between the two nearest entries. The c-th component of each entry is the
+/// transfer value for channel c.
+fn sample_1d(lut: &Lut, r: f32, g: f32, b: f32, size: usize) -> (f32, f32, f32) {
+    let max_index = size - 1;
+    let scale = max_index as f32;
+
+    let curve = |v: f32, c: usize| -> f32 {
+        let vf = (v.clamp(0.0, 1.0) * scale).min(scale);
+        let i0 = (vf.floor() as usize).min(max_index);
+        let i1 = (i0 + 1).min(max_index);
+        let f = vf - i0 as f32;
+        let lo = lut.data[i0 * 3 + c];
+        let hi = lut.data[i1 * 3 + c];
+        lo + (hi - lo) * f
+    };
+
+    (curve(r, 0), curve(g, 1), curve(b, 2))
+}
+
+/// Looks up a single RGB sample in a 3D LUT via tetrahedral interpolation.
+///
+/// The lattice is laid out with R varying fastest, matching the `.cube` load
+/// order: `idx = (r + g*size + b*size*size) * 3`.
+fn sample_tetrahedral(lut: &Lut, r: f32, g: f32, b: f32, size: usize, scale: f32) -> (f32, f32, f32) {
+    let max_index = size - 1;
+
+    let rf = (r.clamp(0.0, 1.0) * scale).min(scale);
+    let gf = (g.clamp(0.0, 1.0) * scale).min(scale);
+    let bf = (b.clamp(0.0, 1.0) * scale).min(scale);
+
+    let r0 = (rf.floor() as usize).min(max_index);
+    let g0 = (gf.floor() as usize).min(max_index);
+    let b0 = (bf.floor() as usize).min(max_index);
+    let r1 = (r0 + 1).min(max_index);
+    let g1 = (g0 + 1).min(max_index);
+    let b1 = (b0 + 1).min(max_index);
+
+    let fr = rf - r0 as f32;
+    let fg = gf - g0 as f32;
+    let fb = bf - b0 as f32;
+
+    let corner = |ri: usize, gi: usize, bi: usize| -> (f32, f32, f32) {
+        let idx = (ri + gi * size + bi * size * size) * 3;
+        (lut.data[idx], lut.data[idx + 1], lut.data[idx + 2])
+    };
+
+    let c000 = corner(r0, g0, b0);
+    let c111 = corner(r1, g1, b1);
+
+    // Weighted sum of the four tetrahedron corners chosen by the ordering of
+    // the fractional parts. Each weight multiplies its corner value directly;
+    // the four weights sum to 1.
+    let lerp4 = |w0: f32, c0: (f32, f32, f32), w1: f32, c1: (f32, f32, f32), w2: f32, c2: (f32, f32, f32), w3: f32, c3: (f32, f32, f32)| -> (f32, f32, f32) {
+        (
+            w0 * c0.0 + w1 * c1.0 + w2 * c2.0 + w3 * c3.0,
+            w0 * c0.1 + w1 * c1.1 + w2 * c2.1 + w3 * c3.1,
+            w0 * c0.2 + w1 * c1.2 + w2 * c2.2 + w3 * c3.2,
+        )
+    };
+
+    if fr >= fg && fg >= fb {
+        lerp4(1.0 - fr, c000, fr - fg, corner(r1, g0, b0), fg - fb, corner(r1, g1, b0), fb, c111)
+    } else if fr >= fb && fb >= fg {
+        lerp4(1.0 - fr, c000, fr - fb, corner(r1, g0, b0), fb - fg, corner(r1, g0, b1), fg, c111)
+    } else if fb >= fr && fr >= fg {
+        lerp4(1.0 - fb, c000, fb - fr, corner(r0, g0, b1), fr - fg, corner(r1, g0, b1), fg, c111)
+    } else if fg >= fr && fr >= fb {
+        lerp4(1.0 - fg, c000, fg - fr, corner(r0, g1, b0), fr - fb, corner(r1, g1, b0), fb, c111)
+    } else if fg >= fb && fb >= fr {
+        lerp4(1.0 - fg, c000, fg - fb, corner(r0, g1, b0), fb - fr, corner(r0, g1, b1), fr, c111)
+    } else {
+        lerp4(1.0 - fb, c000, fb - fg, corner(r0, g0, b1), fg - fr, corner(r0, g1, b1), fr, c111)
+    }
 }
 
 pub fn parse_lut_file(path_str: &str) -> Result<Lut> {